@@ -0,0 +1,152 @@
+/*
+ * Per-label duration / inter-arrival profiling, reusing the grouping
+ * `categorize_nf` already produces. Flow durations span microseconds to
+ * tens of minutes, so buckets are log-spaced rather than linear -
+ * linear buckets would spend nearly all their resolution on the long
+ * tail and none on the microsecond-to-second range where most flows
+ * actually live.
+ */
+use crate::nf::NetFlow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const BUCKET_COUNT: usize = 128;
+const MIN_EDGE_US: f64 = 1.0;
+const MAX_EDGE_US: f64 = 40.0 * 60.0 * 1_000_000.0; // 40 minutes
+
+/// Ascending, log-spaced bucket upper bounds in microseconds, covering
+/// roughly 1us to 40min. Each edge is the inclusive upper bound of its
+/// bucket; durations past the last edge fall into the final bucket.
+fn log_spaced_edges_us() -> Vec<i64> {
+    let log_min: f64 = MIN_EDGE_US.ln();
+    let log_max: f64 = MAX_EDGE_US.ln();
+    (0..BUCKET_COUNT)
+        .map(|i| {
+            let t: f64 = i as f64 / (BUCKET_COUNT - 1) as f64;
+            (log_min + t * (log_max - log_min)).exp().round() as i64
+        })
+        .collect()
+}
+
+fn bucket_index(edges_us: &Vec<i64>, value_us: i64) -> usize {
+    edges_us
+        .partition_point(|&edge| edge < value_us)
+        .min(edges_us.len() - 1)
+}
+
+pub struct Histogram {
+    edges_us: Vec<i64>,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        let edges_us: Vec<i64> = log_spaced_edges_us();
+        let counts: Vec<u64> = vec![0; edges_us.len()];
+        Histogram { edges_us, counts }
+    }
+
+    fn add(&mut self, value_us: i64) {
+        self.counts[bucket_index(&self.edges_us, value_us)] += 1;
+    }
+}
+
+impl std::fmt::Display for Histogram {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(formatter, "{:>15} {:>10}", "edge_us", "count")?;
+        for (edge_us, count) in self.edges_us.iter().zip(self.counts.iter()) {
+            writeln!(formatter, "{:>15} {:>10}", edge_us, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_index_clamps_values_below_the_first_edge_to_bucket_zero() {
+        let edges_us: Vec<i64> = log_spaced_edges_us();
+        assert!(0 < edges_us[0], "test assumes the first edge is above zero");
+        assert_eq!(bucket_index(&edges_us, 0), 0);
+    }
+
+    #[test]
+    fn bucket_index_clamps_values_above_the_last_edge_to_the_last_bucket() {
+        let edges_us: Vec<i64> = log_spaced_edges_us();
+        let above_max: i64 = *edges_us.last().unwrap() + 1;
+        assert_eq!(bucket_index(&edges_us, above_max), edges_us.len() - 1);
+    }
+
+    #[test]
+    fn bucket_index_places_an_exact_edge_value_in_its_own_bucket() {
+        let edges_us: Vec<i64> = log_spaced_edges_us();
+        for i in [0, edges_us.len() / 2, edges_us.len() - 1] {
+            assert_eq!(bucket_index(&edges_us, edges_us[i]), i);
+        }
+    }
+}
+
+fn write_histogram_file(histogram: &Histogram, out_path: &String) -> std::io::Result<()> {
+    let file = File::create(out_path)
+        .expect(&format!("Unable to create/edit file {}", out_path).to_string());
+    let mut writer = BufWriter::new(file);
+    write!(writer, "{}", histogram)
+}
+
+/// For every non-empty label group, builds a histogram of flow
+/// durations and writes it to `<out_dir>/<label>.hist`.
+pub fn write_duration_histograms(
+    categorized_nf_records: &Vec<Vec<NetFlow>>,
+    out_dir: &String,
+) -> std::io::Result<()> {
+    for group in categorized_nf_records {
+        if group.is_empty() {
+            continue;
+        }
+
+        let mut histogram: Histogram = Histogram::new();
+        for nf in group {
+            histogram.add(nf.duration_us());
+        }
+
+        let label_name = group[0].label().name();
+        let out_path: String = format!("{}/{}.hist", out_dir, label_name);
+        write_histogram_file(&histogram, &out_path)?;
+    }
+
+    Ok(())
+}
+
+/// For every label group with at least two flows, builds a histogram of
+/// the inter-arrival gaps between successive flows of that label
+/// (flows are sorted by timestamp first) and writes it to
+/// `<out_dir>/<label>.interarrival.hist`.
+pub fn write_inter_arrival_histograms(
+    categorized_nf_records: &Vec<Vec<NetFlow>>,
+    out_dir: &String,
+) -> std::io::Result<()> {
+    for group in categorized_nf_records {
+        if group.len() < 2 {
+            continue;
+        }
+
+        let mut timestamps_us: Vec<i64> = group
+            .iter()
+            .map(|nf| nf.timestamp().naive().and_utc().timestamp_micros())
+            .collect();
+        timestamps_us.sort_unstable();
+
+        let mut histogram: Histogram = Histogram::new();
+        for pair in timestamps_us.windows(2) {
+            histogram.add((pair[1] - pair[0]).max(0));
+        }
+
+        let label_name = group[0].label().name();
+        let out_path: String = format!("{}/{}.interarrival.hist", out_dir, label_name);
+        write_histogram_file(&histogram, &out_path)?;
+    }
+
+    Ok(())
+}
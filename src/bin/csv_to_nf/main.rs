@@ -1,149 +1,211 @@
 use cic2nf::{
-    cic::reader::read_ids_csv,
-    nf::{categorize_nf, cic_to_nf_batch, write_nf_file, NetFlow},
+    analysis::{write_duration_histograms, write_inter_arrival_histograms},
+    cic::{reader::read_ids_csv, DatasetSchema},
+    nf::{cic_to_nf_batch, categorize_nf, OverwritePolicy},
+    pipeline::{stream_convert_files_to_nf_files, stream_convert_to_nf_files, ConvertOptions},
 };
+use chrono::{DateTime, NaiveDateTime};
+use clap::{Args, Parser};
 use glob::glob;
 
-fn convert_cic_file_to_nf_files(
-    in_path: &String,
-    out_dir: &String,
-    is_am: &Option<bool>,
-    benign_label_name: &String,
-) {
-    let (cic_records, label_library) = read_ids_csv(in_path, is_am, benign_label_name)
-        .expect(&format!("Unable to load {}", in_path).as_str());
+fn parse_dataset_type(value: &str) -> Result<&'static DatasetSchema, String> {
+    cic2nf::cic::schema_by_name(value)
+        .ok_or_else(|| format!("Unknown dataset type '{}' (known types: IDS-2017, DDoS-2019)", value))
+}
+
+fn parse_rfc3339(value: &str) -> Result<NaiveDateTime, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.naive_utc())
+        .map_err(|e| format!("Invalid RFC3339 timestamp '{}': {}", value, e))
+}
 
-    let nf_records: Vec<NetFlow> = cic_to_nf_batch(&cic_records)
-        .expect(&format!("Unable to convert CICRecord's in {} to NetFlow's.", out_dir).to_string());
+#[derive(Parser, Debug)]
+#[command(
+    name = "csv_to_nf",
+    about = "Convert CIC datasets in CSV files to categorized NetFlow v5 files."
+)]
+enum Cli {
+    /// Convert a single CIC CSV file to categorized .nf files
+    Convert(ConvertArgs),
+    /// Recursively convert every .csv file under a directory, sharing one label library
+    ConvertRecursive(ConvertArgs),
+    /// Profile a CIC CSV file's per-label flow-duration and inter-arrival histograms
+    Analyze(AnalyzeArgs),
+}
 
-    std::fs::create_dir_all(out_dir)
-        .expect(&format!("Unable to create output directory {}", out_dir).to_string());
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// Dataset column layout, e.g. IDS-2017 or DDoS-2019
+    #[arg(value_parser = parse_dataset_type)]
+    dataset_type: &'static DatasetSchema,
 
-    let categorized_nf_records: Vec<Vec<NetFlow>> = categorize_nf(nf_records, label_library);
+    /// Label name used for benign (non-attack) flows
+    benign_label_name: String,
 
-    for nf_one_category in categorized_nf_records {
-        if nf_one_category.is_empty() {
-            continue;
-        }
-        let label_name = nf_one_category[0].label().name();
-        let out_path: String = format!("{}/{}.nf", out_dir, label_name);
-        write_nf_file(&nf_one_category, &out_path);
-    }
+    /// Directory to write categorized .nf files into
+    out_dir: String,
+
+    /// CSV file to convert (`convert`), or directory to scan recursively (`convert-recursive`)
+    in_path: String,
+
+    /// Interpret ambiguous 12-hour timestamps as AM
+    #[arg(long, conflicts_with = "pm")]
+    am: bool,
+
+    /// Interpret ambiguous 12-hour timestamps as PM
+    #[arg(long, conflicts_with = "am")]
+    pm: bool,
+
+    /// Only convert flows at or after this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<NaiveDateTime>,
+
+    /// Only convert flows at or before this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    end: Option<NaiveDateTime>,
+
+    /// Overwrite existing .nf files without prompting
+    #[arg(long, conflicts_with = "no_clobber")]
+    force: bool,
+
+    /// Skip existing .nf files without prompting
+    #[arg(long, conflicts_with = "force")]
+    no_clobber: bool,
+
+    /// Also write a binary .nfa archive (with a timestamp index) per label, alongside the .nf files
+    #[arg(long)]
+    archive: bool,
 }
 
-const VALID_OPTIONS: [&'static str; 1] = ["-R"];
+impl ConvertArgs {
+    fn is_am(&self) -> Option<bool> {
+        if self.am {
+            Some(true)
+        } else if self.pm {
+            Some(false)
+        } else {
+            None
+        }
+    }
 
-fn test_options(options: &Vec<String>) {
-    let unknown_options = options
-        .into_iter()
-        .filter(|a| !VALID_OPTIONS.contains(&&a.as_str()));
-    if unknown_options.clone().count() == 0 {
-        return;
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        if self.force {
+            OverwritePolicy::Force
+        } else if self.no_clobber {
+            OverwritePolicy::NoClobber
+        } else {
+            OverwritePolicy::Prompt
+        }
     }
 
-    let mut output: String = String::from("Unknown option(s): ");
-    for option in unknown_options {
-        output.push_str(option.as_str());
-        output.push_str(", ");
+    fn to_options(&self) -> ConvertOptions {
+        ConvertOptions {
+            is_am: self.is_am(),
+            start_time: self.start,
+            end_time: self.end,
+            benign_label_name: self.benign_label_name.clone(),
+            overwrite: self.overwrite_policy(),
+            archive: self.archive,
+        }
     }
+}
 
-    // remove trailing ", "
-    output.pop();
-    output.pop();
+#[derive(Args, Debug)]
+struct AnalyzeArgs {
+    /// Dataset column layout, e.g. IDS-2017 or DDoS-2019
+    #[arg(value_parser = parse_dataset_type)]
+    dataset_type: &'static DatasetSchema,
 
-    panic!("{}", output);
-}
+    /// Label name used for benign (non-attack) flows
+    benign_label_name: String,
 
-fn get_help_message(program_name: &String) -> String {
-    const INFO: &'static str =
-        "Convert CIC datasets in CSV files to categorized NetFlow v5 files.\n";
-
-    let usage: String = format!(
-        "Usage:\n  {} {}",
-        program_name, "[-R] <type> <benign_label_name> <out_dir> <in_path> [is_am]",
-    );
-
-    let example_single: String = format!(
-        "Example (load single csv file):\n  {} {}",
-        program_name, "IDS-2017 BENIGN nf-dir input/data.csv y",
-    );
-
-    let example_recursive: String = format!(
-        "Example (load csv files recursively):\n  {} {}",
-        program_name, "-R DDoS-2019 benign out/nf-dir csv-dir",
-    );
-
-    return format!(
-        "{}\n\n{}\n\n{}\n\n{}\n",
-        INFO, usage, example_single, example_recursive
-    );
-}
+    /// Directory to write <label>.hist / <label>.interarrival.hist files into
+    out_dir: String,
 
-fn main() {
-    // load command-line arguments
-    let args: Vec<String> = std::env::args().collect();
+    /// CSV file to profile
+    in_path: String,
 
-    // extract options
-    let options = args
-        .clone()
-        .into_iter()
-        .filter(|a| a.starts_with('-'))
-        .collect();
+    /// Interpret ambiguous 12-hour timestamps as AM
+    #[arg(long, conflicts_with = "pm")]
+    am: bool,
 
-    test_options(&options);
+    /// Interpret ambiguous 12-hour timestamps as PM
+    #[arg(long, conflicts_with = "am")]
+    pm: bool,
 
-    let mut to_scan_dir: bool = false;
-    if options.contains(&String::from("-R")) {
-        to_scan_dir = true;
-    }
+    /// Only profile flows at or after this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<NaiveDateTime>,
 
-    // extract parameters: <executable> <benign_label_name> <out_dir> <in_path> [is_am]
-    let parameters: Vec<String> = args.into_iter().filter(|a| !a.starts_with('-')).collect();
-    let p_len = parameters.len();
+    /// Only profile flows at or before this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    end: Option<NaiveDateTime>,
+}
 
-    if p_len == 1 {
-        println!("{}", get_help_message(&parameters[0]));
-        return;
+impl AnalyzeArgs {
+    fn is_am(&self) -> Option<bool> {
+        if self.am {
+            Some(true)
+        } else if self.pm {
+            Some(false)
+        } else {
+            None
+        }
     }
+}
 
-    if (p_len != 4) && (p_len != 5) {
-        panic!(
-            "{}\n\n{}",
-            "Error: Incorrect number of parameters.",
-            get_help_message(&parameters[0])
-        );
-    }
+fn analyze_cic_file(args: &AnalyzeArgs) {
+    let (cic_records, label_map) = read_ids_csv(
+        &args.in_path,
+        args.dataset_type,
+        &args.is_am(),
+        &args.benign_label_name,
+        args.start,
+        args.end,
+    )
+    .expect(&format!("Unable to read {}", args.in_path).as_str());
+
+    let nf_records = cic_to_nf_batch(&cic_records)
+        .expect(&format!("Unable to convert CIC records from {}", args.in_path).as_str());
+    let categorized_nf_records = categorize_nf(nf_records, label_map);
+
+    std::fs::create_dir_all(&args.out_dir)
+        .expect(&format!("Unable to create output directory {}", args.out_dir).as_str());
+
+    write_duration_histograms(&categorized_nf_records, &args.out_dir)
+        .expect("Unable to write duration histograms");
+    write_inter_arrival_histograms(&categorized_nf_records, &args.out_dir)
+        .expect("Unable to write inter-arrival histograms");
+}
+
+fn convert_cic_file_to_nf_files(args: &ConvertArgs) {
+    stream_convert_to_nf_files(&args.in_path, args.dataset_type, &args.to_options(), &args.out_dir)
+        .expect(&format!("Unable to convert {}", args.in_path).as_str());
+}
 
-    // store parameters
-    let dataset_name: String = parameters[1].clone();
-    let benign_label_name: String = parameters[2].clone();
-    let output_dir: String = parameters[3].clone();
-    let mut input_path: String = parameters[4].clone();
-    let is_am: Option<bool> = if p_len == 5 {
-        None
-    } else {
-        Some(parameters[5] == "y")
-    };
-
-    if dataset_name != "CIC-IDS-2017" {
-        todo!();
+fn convert_cic_dir_to_nf_files(args: &ConvertArgs) {
+    let glob_pattern: String = format!("{}/**/*.csv", args.in_path);
+    let mut csv_paths: Vec<String> = Vec::new();
+    for entry in glob(glob_pattern.as_str()).expect("Failed to read a glob pattern") {
+        match entry {
+            Ok(path) => csv_paths.push(path.display().to_string()),
+            Err(e) => println!("{:?}", e),
+        }
     }
 
-    // deal with single-csv version
-    if !to_scan_dir {
-        convert_cic_file_to_nf_files(&input_path, &output_dir, &is_am, &benign_label_name);
+    if csv_paths.is_empty() {
+        println!("Warning: No CSV files found under {}", args.in_path);
         return;
     }
 
-    // deal with multiple-csv version
-    println!("Not implemented yet: -R");
-    println!("Found CSV files:");
-    input_path.push_str("/**/*.csv");
-    for entry in glob(input_path.as_str()).expect("Failed to read a glob pattern") {
-        match entry {
-            Ok(path) => println!("{:?}", path.display()),
-            Err(e) => println!("{:?}", e),
-        }
+    stream_convert_files_to_nf_files(&csv_paths, args.dataset_type, &args.to_options(), &args.out_dir)
+        .expect(&format!("Unable to convert CSV files under {}", args.in_path).as_str());
+}
+
+fn main() {
+    match Cli::parse() {
+        Cli::Convert(args) => convert_cic_file_to_nf_files(&args),
+        Cli::ConvertRecursive(args) => convert_cic_dir_to_nf_files(&args),
+        Cli::Analyze(args) => analyze_cic_file(&args),
     }
-    todo!();
 }
@@ -1,5 +1,77 @@
 use chrono::{Duration, NaiveDateTime};
 
+/*
+ * Describes where each field of a CIC record lives in a dataset's CSV
+ * columns, since CSE-CIC-IDS2018, CIC-DDoS-2019, etc. all ship slightly
+ * different column layouts around the same underlying CICFlowMeter fields.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct DatasetSchema {
+    pub name: &'static str,
+    pub record_len: usize,
+    pub src_ip_col: usize,
+    pub src_port_col: usize,
+    pub dst_ip_col: usize,
+    pub dst_port_col: usize,
+    pub protocol_col: usize,
+    pub timestamp_col: usize,
+    pub duration_col: usize,
+    pub fwd_packet_col: usize,
+    pub bwd_packet_col: usize,
+    pub fwd_bytes_col: usize,
+    pub bwd_bytes_col: usize,
+    pub label_col: usize,
+}
+
+pub const CIC_IDS_2017: DatasetSchema = DatasetSchema {
+    name: "IDS-2017",
+    record_len: 85,
+    src_ip_col: 1,
+    src_port_col: 2,
+    dst_ip_col: 3,
+    dst_port_col: 4,
+    protocol_col: 5,
+    timestamp_col: 6,
+    duration_col: 7,
+    fwd_packet_col: 8,
+    bwd_packet_col: 40,
+    fwd_bytes_col: 10,
+    bwd_bytes_col: 11,
+    label_col: 84,
+};
+
+// CIC-DDoS2019's CSVs prepend an extra, unlabeled row-index column that
+// IDS-2017's don't have (shifting every other column right by one), and
+// append two extra trailing feature columns ("SimillarHTTP", "Inbound")
+// between the last IDS-2017-shared column and Label.
+pub const CIC_DDOS_2019: DatasetSchema = DatasetSchema {
+    name: "DDoS-2019",
+    record_len: 88,
+    src_ip_col: 2,
+    src_port_col: 3,
+    dst_ip_col: 4,
+    dst_port_col: 5,
+    protocol_col: 6,
+    timestamp_col: 7,
+    duration_col: 8,
+    fwd_packet_col: 9,
+    bwd_packet_col: 41,
+    fwd_bytes_col: 11,
+    bwd_bytes_col: 12,
+    label_col: 87,
+};
+
+const SCHEMAS: [&DatasetSchema; 2] = [&CIC_IDS_2017, &CIC_DDOS_2019];
+
+/**
+Look up a dataset's column layout by its dataset-type name, e.g.
+"IDS-2017" or "DDoS-2019". Returns `None` for unknown dataset names so
+callers can report an error instead of panicking with `todo!()`.
+*/
+pub fn schema_by_name(name: &str) -> Option<&'static DatasetSchema> {
+    SCHEMAS.iter().find(|schema| schema.name == name).copied()
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct FlowTimeStamp {
     time: NaiveDateTime,
@@ -11,6 +83,12 @@ impl std::fmt::Display for FlowTimeStamp {
     }
 }
 
+impl FlowTimeStamp {
+    pub fn naive(&self) -> NaiveDateTime {
+        self.time
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Label {
     index: u8, // 0 for no index, 1 for benign
@@ -53,31 +131,38 @@ pub struct CICRecord {
 impl CICRecord {
     pub fn from_ids_csv(
         record: &csv::StringRecord,
+        schema: &DatasetSchema,
         is_am: &Option<bool>,
         guessed_time_format_index: usize,
     ) -> (CICRecord, usize) {
-        let (timestamp, actual_time_format_index) =
-            CICRecord::str_to_timestamp(record[6].trim(), is_am, guessed_time_format_index);
+        let (timestamp, actual_time_format_index) = CICRecord::str_to_timestamp(
+            record[schema.timestamp_col].trim(),
+            is_am,
+            guessed_time_format_index,
+        );
 
         let cic_record: CICRecord = CICRecord {
-            src_ip: String::from(record[1].trim()),
-            src_port: record[2].parse().unwrap(),
-            dst_ip: String::from(record[3].trim()),
-            dst_port: record[4].parse().unwrap(),
-            protocol: record[5].parse().unwrap(),
+            src_ip: String::from(record[schema.src_ip_col].trim()),
+            src_port: record[schema.src_port_col].parse().unwrap(),
+            dst_ip: String::from(record[schema.dst_ip_col].trim()),
+            dst_port: record[schema.dst_port_col].parse().unwrap(),
+            protocol: record[schema.protocol_col].parse().unwrap(),
             timestamp,
-            duration: Duration::microseconds(record[7].parse().unwrap()),
+            duration: Duration::microseconds(record[schema.duration_col].parse().unwrap()),
             n_packet: [
-                Self::str_sum_i32(&record[8], &record[40]),
-                Self::str_sum_i32(&record[9], &record[41]),
+                Self::str_sum_i32(&record[schema.fwd_packet_col], &record[schema.bwd_packet_col]),
+                Self::str_sum_i32(
+                    &record[schema.fwd_packet_col + 1],
+                    &record[schema.bwd_packet_col + 1],
+                ),
             ],
             n_bytes_packet: [
-                record[10].parse::<f32>().unwrap() as i32,
-                record[11].parse::<f32>().unwrap() as i32,
+                record[schema.fwd_bytes_col].parse::<f32>().unwrap() as i32,
+                record[schema.bwd_bytes_col].parse::<f32>().unwrap() as i32,
             ],
             label: Label {
                 index: 0,
-                name: String::from(record[84].trim()),
+                name: String::from(record[schema.label_col].trim()),
             },
         };
 
@@ -187,16 +272,20 @@ impl CICRecord {
 }
 
 pub mod reader {
-    use super::CICRecord;
+    use super::{CICRecord, DatasetSchema};
 
+    use chrono::NaiveDateTime;
     use csv::{Reader, ReaderBuilder};
     use std::collections::HashMap;
     use std::fs::File;
 
     pub fn read_ids_csv(
         path_string: &String,
+        schema: &DatasetSchema,
         is_am: &Option<bool>,
         benign_label_name: &String,
+        start_time: Option<NaiveDateTime>,
+        end_time: Option<NaiveDateTime>,
     ) -> std::io::Result<(Vec<CICRecord>, HashMap<String, u8>)> {
         let mut csv_reader: Reader<File> = ReaderBuilder::new()
             .has_headers(true)
@@ -210,28 +299,145 @@ pub mod reader {
         let mut time_format_index: usize = 0;
         for record in csv_reader.records() {
             let str_record: csv::StringRecord = record?;
-            if str_record.len() != 85 {
+            if str_record.len() != schema.record_len {
                 println!("Warning: Skipped CSV record: {:?}", str_record);
                 continue;
             }
             (cic_record, time_format_index) =
-                CICRecord::from_ids_csv(&str_record, is_am, time_format_index);
-            update_label_and_index_mut(&mut label_map, &mut cic_record);
+                CICRecord::from_ids_csv(&str_record, schema, is_am, time_format_index);
+
+            let timestamp: NaiveDateTime = cic_record.timestamp().naive();
+            if start_time.is_some_and(|t| timestamp < t) || end_time.is_some_and(|t| timestamp > t)
+            {
+                continue;
+            }
+
+            update_label_and_index_mut(&mut label_map, &mut cic_record)?;
             cic_record_storage.push(cic_record);
         }
 
         return Ok((cic_record_storage, label_map));
     }
 
-    fn update_label_and_index_mut(label_map: &mut HashMap<String, u8>, cic_record: &mut CICRecord) {
+    /// The index a new, not-yet-seen label would get if inserted into
+    /// `label_map` now (1-based, following `benign_label_name` at index 1).
+    /// Errors instead of silently wrapping once a label library grows past
+    /// what a `u8` index can represent.
+    pub(crate) fn next_label_index(label_map: &HashMap<String, u8>) -> std::io::Result<u8> {
+        u8::try_from(label_map.len() + 1).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Too many distinct labels ({}); at most {} are supported",
+                    label_map.len() + 1,
+                    u8::MAX
+                ),
+            )
+        })
+    }
+
+    pub(crate) fn update_label_and_index_mut(
+        label_map: &mut HashMap<String, u8>,
+        cic_record: &mut CICRecord,
+    ) -> std::io::Result<()> {
         let current_label: &String = &cic_record.label().name();
-        match label_map.get(current_label) {
-            Some(index) => *cic_record.label_mut().index_mut() = *index,
+        let index: u8 = match label_map.get(current_label) {
+            Some(index) => *index,
             None => {
-                let current_index: u8 = (label_map.len() + 1) as u8;
-                label_map.insert(current_label.clone(), current_index);
-                *cic_record.label_mut().index_mut() = current_index;
+                let index: u8 = next_label_index(label_map)?;
+                label_map.insert(current_label.clone(), index);
+                index
             }
+        };
+        *cic_record.label_mut().index_mut() = index;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::cic::CIC_IDS_2017;
+
+        #[test]
+        fn next_label_index_errors_instead_of_wrapping_past_255_labels() {
+            let mut label_map: HashMap<String, u8> = HashMap::new();
+            for i in 0..255u16 {
+                let index: u8 = next_label_index(&label_map).unwrap();
+                label_map.insert(format!("label-{}", i), index);
+            }
+            assert_eq!(label_map.len(), 255);
+
+            let result = next_label_index(&label_map);
+            assert!(
+                result.is_err(),
+                "the 256th label should be rejected, not wrapped"
+            );
         }
+
+        #[test]
+        fn update_label_and_index_mut_reuses_the_index_for_a_known_label() {
+            let mut label_map: HashMap<String, u8> = HashMap::from([("Benign".to_string(), 1)]);
+            let mut fields: Vec<String> = vec!["0".to_string(); CIC_IDS_2017.record_len];
+            fields[CIC_IDS_2017.timestamp_col] = "01/01/2019 12:00".to_string();
+            fields[CIC_IDS_2017.label_col] = "Benign".to_string();
+            let str_record = csv::StringRecord::from(fields);
+            let (mut cic_record, _) =
+                CICRecord::from_ids_csv(&str_record, &CIC_IDS_2017, &Some(true), 0);
+
+            update_label_and_index_mut(&mut label_map, &mut cic_record).unwrap();
+
+            assert_eq!(cic_record.label().index(), 1);
+            assert_eq!(label_map.len(), 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `StringRecord` matching `schema`'s column layout, with only
+    /// the columns `CICRecord::from_ids_csv` actually reads filled in.
+    fn sample_fields(schema: &DatasetSchema, label: &str) -> csv::StringRecord {
+        let mut fields: Vec<String> = vec!["0".to_string(); schema.record_len];
+        fields[schema.src_ip_col] = "10.0.0.1".to_string();
+        fields[schema.src_port_col] = "1234".to_string();
+        fields[schema.dst_ip_col] = "10.0.0.2".to_string();
+        fields[schema.dst_port_col] = "80".to_string();
+        fields[schema.protocol_col] = "6".to_string();
+        fields[schema.timestamp_col] = "01/01/2019 12:00".to_string();
+        fields[schema.label_col] = label.to_string();
+        csv::StringRecord::from(fields)
+    }
+
+    #[test]
+    fn schema_by_name_resolves_known_dataset_names() {
+        assert_eq!(schema_by_name("IDS-2017").unwrap().name, CIC_IDS_2017.name);
+        assert_eq!(schema_by_name("DDoS-2019").unwrap().name, CIC_DDOS_2019.name);
+        assert!(schema_by_name("IDS-2018").is_none());
+    }
+
+    #[test]
+    fn from_ids_csv_reads_ids_2017_columns() {
+        let str_record = sample_fields(&CIC_IDS_2017, "Benign");
+        let (cic_record, _) = CICRecord::from_ids_csv(&str_record, &CIC_IDS_2017, &Some(true), 0);
+
+        assert_eq!(cic_record.src_ip(), "10.0.0.1");
+        assert_eq!(*cic_record.src_port(), 1234);
+        assert_eq!(cic_record.dst_ip(), "10.0.0.2");
+        assert_eq!(*cic_record.dst_port(), 80);
+        assert_eq!(cic_record.label().name(), "Benign");
+    }
+
+    #[test]
+    fn from_ids_csv_reads_ddos_2019_columns_shifted_past_its_extra_columns() {
+        let str_record = sample_fields(&CIC_DDOS_2019, "DrDoS_DNS");
+        let (cic_record, _) = CICRecord::from_ids_csv(&str_record, &CIC_DDOS_2019, &Some(true), 0);
+
+        assert_eq!(cic_record.src_ip(), "10.0.0.1");
+        assert_eq!(*cic_record.src_port(), 1234);
+        assert_eq!(cic_record.dst_ip(), "10.0.0.2");
+        assert_eq!(*cic_record.dst_port(), 80);
+        assert_eq!(cic_record.label().name(), "DrDoS_DNS");
     }
 }
@@ -0,0 +1,4 @@
+pub mod analysis;
+pub mod cic;
+pub mod nf;
+pub mod pipeline;
@@ -3,8 +3,8 @@ use chrono::Duration;
 use std::{
     cmp::max,
     collections::HashMap,
-    fs::File,
-    io::{BufWriter, Write},
+    io::{stdin, Write},
+    path::Path,
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -130,6 +130,14 @@ impl NetFlow {
         return self.duration.num_milliseconds();
     }
 
+    pub fn duration_us(&self) -> i64 {
+        return self.duration.num_microseconds().unwrap_or(0);
+    }
+
+    pub fn timestamp(&self) -> &FlowTimeStamp {
+        return &self.timestamp;
+    }
+
     pub fn duration_str_width_mut(&mut self) -> &mut u8 {
         &mut self.duration_str_width
     }
@@ -182,6 +190,19 @@ fn get_n_digit_in_decimal(mut x: i64) -> u8 {
     return n;
 }
 
+/**
+Width (in characters) of the formatted "seconds.millis" duration column
+that keeps every row lined up, given the largest duration (in ms) that
+will be printed.
+*/
+pub(crate) fn duration_str_width_for_max_ms(max_duration_ms: i64) -> u8 {
+    let mut duration_width = get_n_digit_in_decimal(max_duration_ms) + 1;
+    if max_duration_ms < 1000 {
+        duration_width += 1;
+    };
+    duration_width
+}
+
 pub fn cic_to_nf_batch(cic_records: &Vec<CICRecord>) -> std::io::Result<Vec<NetFlow>> {
     let mut netflow_storage: Vec<NetFlow> = Vec::new();
     let mut max_duration_ms: i64 = 0;
@@ -193,10 +214,7 @@ pub fn cic_to_nf_batch(cic_records: &Vec<CICRecord>) -> std::io::Result<Vec<NetF
         netflow_storage.push(nf2);
     }
 
-    let mut duration_width = get_n_digit_in_decimal(max_duration_ms) + 1;
-    if max_duration_ms < 1000 {
-        duration_width += 1;
-    };
+    let duration_width = duration_str_width_for_max_ms(max_duration_ms);
 
     for n in &mut netflow_storage {
         *n.duration_str_width_mut() = duration_width;
@@ -204,32 +222,35 @@ pub fn cic_to_nf_batch(cic_records: &Vec<CICRecord>) -> std::io::Result<Vec<NetF
     return Ok(netflow_storage);
 }
 
-pub fn write_nf_file(nf_records: &Vec<NetFlow>, fname: &String) {
-    // FIXME: toggle this function with command-line flags
-    /*if Path::new(fname).exists() {
-        print!("File {} exists. Do you want to overwrite it? [Y/n] ", fname);
-        let mut buffer = String::new();
-        stdin()
-            .read_line(&mut buffer)
-            .expect("Error: Cannot read from stdin.");
-        if buffer == "n" {
-            println!("Skipped file: {}", fname);
-            return;
-        }
-    }*/
-    let of = File::create(fname.to_string())
-        .expect(&format!("Unable to create/edit file {}", fname).to_string());
-
-    let mut ob = BufWriter::new(of);
-
-    for line in nf_records {
-        writeln!(ob, "{}", line).expect(
-            &format!(
-                "Unable to write the following content to file {}\n{}",
-                fname, line
-            )
-            .to_string(),
-        );
+/// How to handle a `.nf`/`.nfa` output file that already exists.
+#[derive(Clone, Copy, Debug)]
+pub enum OverwritePolicy {
+    /// Ask on stdin whether to overwrite.
+    Prompt,
+    /// Always overwrite without asking.
+    Force,
+    /// Never overwrite; skip the file without asking.
+    NoClobber,
+}
+
+/// Whether `fname` should be (re)written, applying `policy` if it already exists.
+pub(crate) fn should_overwrite(fname: &String, policy: OverwritePolicy) -> bool {
+    if !Path::new(fname).exists() {
+        return true;
+    }
+
+    match policy {
+        OverwritePolicy::Force => true,
+        OverwritePolicy::NoClobber => false,
+        OverwritePolicy::Prompt => {
+            print!("File {} exists. Do you want to overwrite it? [y/N] ", fname);
+            std::io::stdout().flush().ok();
+            let mut buffer = String::new();
+            stdin()
+                .read_line(&mut buffer)
+                .expect("Error: Cannot read from stdin.");
+            buffer.trim().eq_ignore_ascii_case("y")
+        }
     }
 }
 
@@ -248,3 +269,369 @@ pub fn categorize_nf(
 
     return categorized_records;
 }
+
+/*
+ * Compact append-only binary alternative to the fixed-width text `.nf`
+ * files the streaming conversion pipeline produces: a small header, then
+ * fixed-size records, then a trailing index of (timestamp, byte offset) pairs
+ * sampled every `INDEX_INTERVAL` records so a reader can binary-search
+ * to a time and scan forward instead of reading the whole file.
+ */
+pub mod archive {
+    use super::{Flags, NetFlow};
+    use memmap2::Mmap;
+    use std::fs::File;
+    use std::io::{self, BufWriter, ErrorKind, Write};
+    use std::net::Ipv4Addr;
+
+    const MAGIC: &[u8; 4] = b"NFA1";
+    const FORMAT_VERSION: u16 = 1;
+    const RECORD_LAYOUT_VERSION: u16 = 1;
+    const HEADER_SIZE: usize = 4 + 2 + 2;
+    const RECORD_SIZE: usize = 51;
+    const INDEX_ENTRY_SIZE: usize = 8 + 8;
+    const FOOTER_SIZE: usize = 8;
+
+    /// Sample one (timestamp, byte offset) index entry every this many records.
+    const INDEX_INTERVAL: u64 = 1024;
+
+    fn invalid_data(message: &str) -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, message.to_string())
+    }
+
+    fn parse_ipv4(ip: &str) -> io::Result<u32> {
+        ip.parse::<Ipv4Addr>()
+            .map(u32::from)
+            .map_err(|_| invalid_data(&format!("Not an IPv4 address: {}", ip)))
+    }
+
+    impl Flags {
+        fn pack(&self) -> u8 {
+            (self.cwr as u8) << 7
+                | (self.ece as u8) << 6
+                | (self.urg as u8) << 5
+                | (self.ack as u8) << 4
+                | (self.psh as u8) << 3
+                | (self.rst as u8) << 2
+                | (self.syn as u8) << 1
+                | (self.fin as u8)
+        }
+
+        fn unpack(packed: u8) -> Flags {
+            Flags {
+                cwr: packed & (1 << 7) != 0,
+                ece: packed & (1 << 6) != 0,
+                urg: packed & (1 << 5) != 0,
+                ack: packed & (1 << 4) != 0,
+                psh: packed & (1 << 3) != 0,
+                rst: packed & (1 << 2) != 0,
+                syn: packed & (1 << 1) != 0,
+                fin: packed & 1 != 0,
+            }
+        }
+    }
+
+    fn encode_record(nf: &NetFlow, buf: &mut [u8; RECORD_SIZE]) -> io::Result<()> {
+        let timestamp_us: i64 = nf.timestamp.naive().and_utc().timestamp_micros();
+        let duration_us: i64 = nf.duration.num_microseconds().unwrap_or(0);
+        let src_ip: u32 = parse_ipv4(&nf.src_ip)?;
+        let dst_ip: u32 = parse_ipv4(&nf.dst_ip)?;
+
+        let mut offset: usize = 0;
+        macro_rules! put {
+            ($bytes:expr) => {{
+                let bytes = $bytes;
+                buf[offset..offset + bytes.len()].copy_from_slice(&bytes);
+                offset += bytes.len();
+            }};
+        }
+
+        put!(timestamp_us.to_le_bytes());
+        put!(duration_us.to_le_bytes());
+        put!([nf.protocol]);
+        put!(src_ip.to_le_bytes());
+        put!(nf.src_port.to_le_bytes());
+        put!(dst_ip.to_le_bytes());
+        put!(nf.dst_port.to_le_bytes());
+        put!([nf.flags.pack()]);
+        put!(nf.qos.to_le_bytes());
+        put!(nf.n_packet.to_le_bytes());
+        put!(nf.n_bytes_packet.to_le_bytes());
+        put!(nf.n_flow.to_le_bytes());
+        put!([nf.label.index()]);
+
+        debug_assert_eq!(offset, RECORD_SIZE);
+        Ok(())
+    }
+
+    /// A `NetFlow` reconstructed from an [`ArchiveReader`], with IP addresses
+    /// unpacked into `Ipv4Addr`s and flags back into their bool-set form.
+    #[derive(Clone, Copy, Debug)]
+    pub struct ArchivedRecord {
+        pub timestamp_us: i64,
+        pub duration_us: i64,
+        pub protocol: u8,
+        pub src_ip: Ipv4Addr,
+        pub src_port: u32,
+        pub dst_ip: Ipv4Addr,
+        pub dst_port: u32,
+        pub flags: Flags,
+        pub qos: f32,
+        pub n_packet: u32,
+        pub n_bytes_packet: u32,
+        pub n_flow: u32,
+        pub label_index: u8,
+    }
+
+    fn decode_record(buf: &[u8]) -> ArchivedRecord {
+        ArchivedRecord {
+            timestamp_us: i64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            duration_us: i64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            protocol: buf[16],
+            src_ip: Ipv4Addr::from(u32::from_le_bytes(buf[17..21].try_into().unwrap())),
+            src_port: u32::from_le_bytes(buf[21..25].try_into().unwrap()),
+            dst_ip: Ipv4Addr::from(u32::from_le_bytes(buf[25..29].try_into().unwrap())),
+            dst_port: u32::from_le_bytes(buf[29..33].try_into().unwrap()),
+            flags: Flags::unpack(buf[33]),
+            qos: f32::from_le_bytes(buf[34..38].try_into().unwrap()),
+            n_packet: u32::from_le_bytes(buf[38..42].try_into().unwrap()),
+            n_bytes_packet: u32::from_le_bytes(buf[42..46].try_into().unwrap()),
+            n_flow: u32::from_le_bytes(buf[46..50].try_into().unwrap()),
+            label_index: buf[50],
+        }
+    }
+
+    /// Writes `NetFlow`s to an append-only binary archive, sampling a
+    /// timestamp index as it goes and flushing it as a trailing section
+    /// when the writer is [`finish`](ArchiveWriter::finish)ed.
+    pub struct ArchiveWriter {
+        file: BufWriter<File>,
+        record_count: u64,
+        index: Vec<(i64, u64)>,
+    }
+
+    impl ArchiveWriter {
+        pub fn create(path: &String) -> io::Result<ArchiveWriter> {
+            let file = File::create(path)?;
+            let mut file = BufWriter::new(file);
+            file.write_all(MAGIC)?;
+            file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+            file.write_all(&RECORD_LAYOUT_VERSION.to_le_bytes())?;
+            Ok(ArchiveWriter {
+                file,
+                record_count: 0,
+                index: Vec::new(),
+            })
+        }
+
+        pub fn write_record(&mut self, nf: &NetFlow) -> io::Result<()> {
+            if self.record_count % INDEX_INTERVAL == 0 {
+                let timestamp_us: i64 = nf.timestamp.naive().and_utc().timestamp_micros();
+                let byte_offset: u64 =
+                    HEADER_SIZE as u64 + self.record_count * RECORD_SIZE as u64;
+                self.index.push((timestamp_us, byte_offset));
+            }
+
+            let mut buf = [0u8; RECORD_SIZE];
+            encode_record(nf, &mut buf)?;
+            self.file.write_all(&buf)?;
+            self.record_count += 1;
+            Ok(())
+        }
+
+        /// Appends the trailing index section and its footer, then flushes.
+        pub fn finish(mut self) -> io::Result<()> {
+            let index_offset: u64 = HEADER_SIZE as u64 + self.record_count * RECORD_SIZE as u64;
+            for (timestamp_us, byte_offset) in &self.index {
+                self.file.write_all(&timestamp_us.to_le_bytes())?;
+                self.file.write_all(&byte_offset.to_le_bytes())?;
+            }
+            self.file.write_all(&index_offset.to_le_bytes())?;
+            self.file.flush()
+        }
+    }
+
+    /// Read-only, memory-mapped view over an archive written by
+    /// [`ArchiveWriter`]. Validates the header up front, then either
+    /// iterates every record or seeks to a timestamp via the trailing index.
+    pub struct ArchiveReader {
+        mmap: Mmap,
+        index: Vec<(i64, u64)>,
+        record_count: u64,
+    }
+
+    impl ArchiveReader {
+        pub fn open(path: &String) -> io::Result<ArchiveReader> {
+            let file = File::open(path)?;
+            let mmap = unsafe { Mmap::map(&file)? };
+
+            if mmap.len() < HEADER_SIZE + FOOTER_SIZE {
+                return Err(invalid_data("Archive file is smaller than its header+footer"));
+            }
+            if &mmap[0..4] != MAGIC {
+                return Err(invalid_data("Not a NetFlow archive (bad magic bytes)"));
+            }
+            let format_version = u16::from_le_bytes(mmap[4..6].try_into().unwrap());
+            let record_layout_version = u16::from_le_bytes(mmap[6..8].try_into().unwrap());
+            if format_version != FORMAT_VERSION || record_layout_version != RECORD_LAYOUT_VERSION {
+                return Err(invalid_data(&format!(
+                    "Unsupported archive version: format={}, record_layout={}",
+                    format_version, record_layout_version
+                )));
+            }
+
+            let footer_pos = mmap.len() - FOOTER_SIZE;
+            let index_offset =
+                u64::from_le_bytes(mmap[footer_pos..footer_pos + FOOTER_SIZE].try_into().unwrap());
+            let index_offset = index_offset as usize;
+            if index_offset < HEADER_SIZE || index_offset > footer_pos {
+                return Err(invalid_data("Archive index offset is out of bounds"));
+            }
+
+            let record_count = ((index_offset - HEADER_SIZE) / RECORD_SIZE) as u64;
+
+            let mut index = Vec::new();
+            let mut pos = index_offset;
+            while pos + INDEX_ENTRY_SIZE <= footer_pos {
+                let timestamp_us = i64::from_le_bytes(mmap[pos..pos + 8].try_into().unwrap());
+                let byte_offset = u64::from_le_bytes(mmap[pos + 8..pos + 16].try_into().unwrap());
+                index.push((timestamp_us, byte_offset));
+                pos += INDEX_ENTRY_SIZE;
+            }
+
+            Ok(ArchiveReader {
+                mmap,
+                index,
+                record_count,
+            })
+        }
+
+        pub fn record_count(&self) -> u64 {
+            self.record_count
+        }
+
+        pub fn record_at(&self, i: u64) -> ArchivedRecord {
+            let offset = HEADER_SIZE + (i as usize) * RECORD_SIZE;
+            decode_record(&self.mmap[offset..offset + RECORD_SIZE])
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = ArchivedRecord> + '_ {
+            (0..self.record_count).map(move |i| self.record_at(i))
+        }
+
+        /// Returns an iterator starting at the record of the last sampled
+        /// index entry whose timestamp is at or before `timestamp_us` (or at
+        /// the first record if there is none), found via a binary search over
+        /// the trailing index rather than scanning from the start. Records
+        /// between that entry and `timestamp_us` are yielded too.
+        pub fn iter_from(&self, timestamp_us: i64) -> impl Iterator<Item = ArchivedRecord> + '_ {
+            let start_index: u64 = match self.index.partition_point(|&(ts, _)| ts <= timestamp_us)
+            {
+                0 => 0,
+                n => {
+                    let (_, byte_offset) = self.index[n - 1];
+                    (byte_offset - HEADER_SIZE as u64) / RECORD_SIZE as u64
+                }
+            };
+            (start_index..self.record_count).map(move |i| self.record_at(i))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::cic::{CICRecord, CIC_IDS_2017};
+
+        fn sample_cic_record(minute: u32, label: &str) -> CICRecord {
+            let mut fields: Vec<String> = vec!["0".to_string(); CIC_IDS_2017.record_len];
+            fields[CIC_IDS_2017.src_ip_col] = "10.0.0.1".to_string();
+            fields[CIC_IDS_2017.src_port_col] = "1234".to_string();
+            fields[CIC_IDS_2017.dst_ip_col] = "10.0.0.2".to_string();
+            fields[CIC_IDS_2017.dst_port_col] = "80".to_string();
+            fields[CIC_IDS_2017.protocol_col] = "6".to_string();
+            fields[CIC_IDS_2017.timestamp_col] = format!("01/01/2019 12:{:02}", minute);
+            fields[CIC_IDS_2017.duration_col] = "1000".to_string();
+            fields[CIC_IDS_2017.fwd_packet_col] = "3".to_string();
+            fields[CIC_IDS_2017.fwd_packet_col + 1] = "1".to_string();
+            fields[CIC_IDS_2017.bwd_packet_col] = "2".to_string();
+            fields[CIC_IDS_2017.bwd_packet_col + 1] = "1".to_string();
+            fields[CIC_IDS_2017.fwd_bytes_col] = "100".to_string();
+            fields[CIC_IDS_2017.bwd_bytes_col] = "200".to_string();
+            fields[CIC_IDS_2017.label_col] = label.to_string();
+
+            let str_record = csv::StringRecord::from(fields);
+            let (cic_record, _) = CICRecord::from_ids_csv(&str_record, &CIC_IDS_2017, &Some(true), 0);
+            cic_record
+        }
+
+        fn temp_archive_path(name: &str) -> String {
+            std::env::temp_dir()
+                .join(format!("cic2nf_archive_{}_{}.nfa", name, std::process::id()))
+                .display()
+                .to_string()
+        }
+
+        #[test]
+        fn round_trips_records_through_write_and_read() {
+            let path = temp_archive_path("roundtrip");
+
+            let mut writer = ArchiveWriter::create(&path).unwrap();
+            let mut expected_nfs: Vec<NetFlow> = Vec::new();
+            for minute in 0..10 {
+                let cic_record = sample_cic_record(minute, "Benign");
+                let (nf1, _nf2) = NetFlow::new(&cic_record);
+                writer.write_record(&nf1).unwrap();
+                expected_nfs.push(nf1);
+            }
+            writer.finish().unwrap();
+
+            let reader = ArchiveReader::open(&path).unwrap();
+            std::fs::remove_file(&path).ok();
+
+            assert_eq!(reader.record_count(), expected_nfs.len() as u64);
+            for (actual, expected) in reader.iter().zip(expected_nfs.iter()) {
+                assert_eq!(
+                    actual.timestamp_us,
+                    expected.timestamp().naive().and_utc().timestamp_micros()
+                );
+                assert_eq!(actual.duration_us, expected.duration_us());
+                assert_eq!(actual.protocol, expected.protocol);
+                assert_eq!(actual.src_ip.to_string(), expected.src_ip);
+                assert_eq!(actual.src_port, expected.src_port);
+                assert_eq!(actual.dst_ip.to_string(), expected.dst_ip);
+                assert_eq!(actual.dst_port, expected.dst_port);
+                assert_eq!(actual.n_packet, expected.n_packet);
+                assert_eq!(actual.n_bytes_packet, expected.n_bytes_packet);
+                assert_eq!(actual.n_flow, expected.n_flow);
+                assert_eq!(actual.label_index, expected.label.index());
+            }
+        }
+
+        #[test]
+        fn iter_from_seeks_to_the_sampled_index_entry() {
+            let path = temp_archive_path("iter_from");
+
+            let mut writer = ArchiveWriter::create(&path).unwrap();
+            let mut timestamps_us: Vec<i64> = Vec::new();
+            for minute in 0..10 {
+                let cic_record = sample_cic_record(minute, "Benign");
+                let (nf1, _nf2) = NetFlow::new(&cic_record);
+                timestamps_us.push(nf1.timestamp().naive().and_utc().timestamp_micros());
+                writer.write_record(&nf1).unwrap();
+            }
+            writer.finish().unwrap();
+
+            let reader = ArchiveReader::open(&path).unwrap();
+            std::fs::remove_file(&path).ok();
+
+            // Every record is covered by the single index entry sampled at
+            // record 0 (INDEX_INTERVAL is far larger than 10), so seeking to
+            // any later timestamp should still recover every record from the
+            // start without missing or skipping any.
+            let from_middle: Vec<ArchivedRecord> = reader.iter_from(timestamps_us[5]).collect();
+            assert_eq!(from_middle.len(), timestamps_us.len());
+            assert_eq!(from_middle[0].timestamp_us, timestamps_us[0]);
+            assert_eq!(from_middle[5].timestamp_us, timestamps_us[5]);
+        }
+    }
+}
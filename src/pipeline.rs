@@ -0,0 +1,382 @@
+/*
+ * Streaming convert-and-write pipeline: a CSV row is parsed into a
+ * CICRecord, turned into its two NetFlow's, and written straight to its
+ * label's output file, so the full dataset is never held in memory at
+ * once the way `read_ids_csv` + `cic_to_nf_batch` + `categorize_nf` do.
+ */
+use crate::cic::{
+    reader::{next_label_index, update_label_and_index_mut},
+    CICRecord, DatasetSchema,
+};
+use crate::nf::{
+    archive::ArchiveWriter, duration_str_width_for_max_ms, should_overwrite, NetFlow,
+    OverwritePolicy,
+};
+use chrono::NaiveDateTime;
+use csv::{Reader, ReaderBuilder};
+use std::cmp::max;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{sink, BufWriter, Write};
+
+/**
+Settings shared by every row of every file in one conversion run, bundled
+so the streaming functions below don't each grow their own parallel list
+of positional parameters.
+*/
+pub struct ConvertOptions {
+    pub is_am: Option<bool>,
+    pub start_time: Option<NaiveDateTime>,
+    pub end_time: Option<NaiveDateTime>,
+    pub benign_label_name: String,
+    pub overwrite: OverwritePolicy,
+    /// Also write a binary `.nfa` archive per label, alongside its `.nf` file.
+    pub archive: bool,
+}
+
+/**
+Result of a cheap scan over one CSV file: every distinct label name it
+contains, plus its largest (clamped-to-zero) flow duration in
+milliseconds. Neither requires parsing a full `CICRecord`.
+*/
+struct FileScan {
+    labels: BTreeSet<String>,
+    max_duration_ms: i64,
+}
+
+fn scan_file(path_string: &String, schema: &DatasetSchema) -> std::io::Result<FileScan> {
+    let mut csv_reader: Reader<File> = ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(path_string)
+        .expect(&format!("Unable to read CSV file: {}", path_string).as_str());
+
+    let mut labels: BTreeSet<String> = BTreeSet::new();
+    let mut max_duration_ms: i64 = 0;
+    for record in csv_reader.records() {
+        let str_record: csv::StringRecord = record?;
+        if str_record.len() != schema.record_len {
+            continue;
+        }
+        labels.insert(str_record[schema.label_col].trim().to_string());
+        let duration_us: i64 = str_record[schema.duration_col].parse().unwrap_or(0);
+        max_duration_ms = max(max_duration_ms, max(duration_us, 0) / 1000);
+    }
+
+    Ok(FileScan {
+        labels,
+        max_duration_ms,
+    })
+}
+
+fn writer_for_label<'a>(
+    writers: &'a mut HashMap<u8, BufWriter<Box<dyn Write>>>,
+    out_dir: &String,
+    nf: &NetFlow,
+    overwrite: OverwritePolicy,
+) -> &'a mut BufWriter<Box<dyn Write>> {
+    writers.entry(nf.label().index()).or_insert_with(|| {
+        let out_path: String = format!("{}/{}.nf", out_dir, nf.label().name());
+        let write_target: Box<dyn Write> = if should_overwrite(&out_path, overwrite) {
+            let file: File = File::create(&out_path)
+                .expect(&format!("Unable to create/edit file {}", out_path).to_string());
+            Box::new(file)
+        } else {
+            println!("Skipped file: {}", out_path);
+            Box::new(sink())
+        };
+        BufWriter::new(write_target)
+    })
+}
+
+/**
+Returns the archive writer for `nf`'s label, opening `<out_dir>/<label>.nfa`
+the first time that label is seen. Holds `None` for a label whose archive
+was skipped under `overwrite`, so later records for it are dropped the same
+way a skipped `.nf` writer drops its lines into `sink()`.
+*/
+fn archive_writer_for_label<'a>(
+    archive_writers: &'a mut HashMap<u8, Option<ArchiveWriter>>,
+    out_dir: &String,
+    nf: &NetFlow,
+    overwrite: OverwritePolicy,
+) -> &'a mut Option<ArchiveWriter> {
+    archive_writers.entry(nf.label().index()).or_insert_with(|| {
+        let out_path: String = format!("{}/{}.nfa", out_dir, nf.label().name());
+        if should_overwrite(&out_path, overwrite) {
+            Some(
+                ArchiveWriter::create(&out_path)
+                    .expect(&format!("Unable to create archive file {}", out_path).to_string()),
+            )
+        } else {
+            println!("Skipped file: {}", out_path);
+            None
+        }
+    })
+}
+
+/**
+The label library and open output writers for one conversion run, carried
+across files so a label's `.nf`/`.nfa` files are opened once and appended
+to rather than truncated between files.
+*/
+struct ConversionWriters {
+    label_map: HashMap<String, u8>,
+    writers: HashMap<u8, BufWriter<Box<dyn Write>>>,
+    archive_writers: HashMap<u8, Option<ArchiveWriter>>,
+}
+
+impl ConversionWriters {
+    fn new(benign_label_name: &String) -> ConversionWriters {
+        let benign_label: (String, u8) = (benign_label_name.clone(), 1);
+        ConversionWriters {
+            label_map: HashMap::from([benign_label]),
+            writers: HashMap::new(),
+            archive_writers: HashMap::new(),
+        }
+    }
+
+    /// Flushes every `.nf` writer and finishes every `.nfa` archive writer,
+    /// then returns the final label name -> index map.
+    fn finish(self) -> std::io::Result<HashMap<String, u8>> {
+        for (_, mut writer) in self.writers {
+            writer.flush()?;
+        }
+        for (_, archive_writer) in self.archive_writers {
+            if let Some(archive_writer) = archive_writer {
+                archive_writer.finish()?;
+            }
+        }
+
+        Ok(self.label_map)
+    }
+}
+
+/**
+Stream-convert one CSV file, writing every row's two `NetFlow`s to
+`state`'s writers as soon as they are produced. `state` is threaded in so
+a caller can share it across several files: new labels extend its label
+map in place, and a label's writer is opened once and reused, so later
+files append to it rather than truncating it.
+*/
+fn stream_convert_one_file(
+    csv_path: &String,
+    schema: &DatasetSchema,
+    options: &ConvertOptions,
+    duration_str_width: u8,
+    state: &mut ConversionWriters,
+    out_dir: &String,
+) -> std::io::Result<()> {
+    let mut csv_reader: Reader<File> = ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(csv_path)
+        .expect(&format!("Unable to read CSV file: {}", csv_path).as_str());
+
+    let mut cic_record: CICRecord;
+    let mut time_format_index: usize = 0;
+
+    for record in csv_reader.records() {
+        let str_record: csv::StringRecord = record?;
+        if str_record.len() != schema.record_len {
+            println!("Warning: Skipped CSV record: {:?}", str_record);
+            continue;
+        }
+
+        (cic_record, time_format_index) =
+            CICRecord::from_ids_csv(&str_record, schema, &options.is_am, time_format_index);
+
+        let timestamp: NaiveDateTime = cic_record.timestamp().naive();
+        if options.start_time.is_some_and(|t| timestamp < t)
+            || options.end_time.is_some_and(|t| timestamp > t)
+        {
+            continue;
+        }
+
+        update_label_and_index_mut(&mut state.label_map, &mut cic_record)?;
+
+        let (mut nf1, mut nf2) = NetFlow::new(&cic_record);
+        *nf1.duration_str_width_mut() = duration_str_width;
+        *nf2.duration_str_width_mut() = duration_str_width;
+
+        for nf in [&nf1, &nf2] {
+            let writer = writer_for_label(&mut state.writers, out_dir, nf, options.overwrite);
+            writeln!(writer, "{}", nf).expect(
+                &format!("Unable to write the following content to a .nf file\n{}", nf)
+                    .to_string(),
+            );
+
+            if options.archive {
+                if let Some(archive_writer) = archive_writer_for_label(
+                    &mut state.archive_writers,
+                    out_dir,
+                    nf,
+                    options.overwrite,
+                ) {
+                    archive_writer.write_record(nf).expect(
+                        &format!(
+                            "Unable to write to archive file for label {}",
+                            nf.label().name()
+                        )
+                        .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+Convert a single CIC CSV file to categorized `.nf` files without
+materializing the dataset in memory: a cheap first pass finds the
+largest flow duration for column alignment, then a second pass converts
+and writes each row's two `NetFlow`s as soon as they are produced.
+
+Returns the label name -> index map, seeded with `options.benign_label_name`
+at index 1, the same convention `cic::reader::read_ids_csv` uses.
+*/
+pub fn stream_convert_to_nf_files(
+    csv_path: &String,
+    schema: &DatasetSchema,
+    options: &ConvertOptions,
+    out_dir: &String,
+) -> std::io::Result<HashMap<String, u8>> {
+    std::fs::create_dir_all(out_dir)
+        .expect(&format!("Unable to create output directory {}", out_dir).to_string());
+
+    let scan: FileScan = scan_file(csv_path, schema)?;
+    let duration_str_width: u8 = duration_str_width_for_max_ms(scan.max_duration_ms);
+
+    let mut state: ConversionWriters = ConversionWriters::new(&options.benign_label_name);
+
+    stream_convert_one_file(csv_path, schema, options, duration_str_width, &mut state, out_dir)?;
+
+    state.finish()
+}
+
+/**
+Convert every CSV file in `csv_paths` to categorized `.nf` files that
+share one label library, so the same attack name gets the same index
+regardless of which file it first appears in. Each file is scanned for
+its labels and largest duration in parallel, since that pass touches no
+shared state; the conversion pass that follows runs file by file so that
+every row for a label lands in the same writer, appending across files
+instead of truncating between them.
+*/
+pub fn stream_convert_files_to_nf_files(
+    csv_paths: &[String],
+    schema: &DatasetSchema,
+    options: &ConvertOptions,
+    out_dir: &String,
+) -> std::io::Result<HashMap<String, u8>> {
+    std::fs::create_dir_all(out_dir)
+        .expect(&format!("Unable to create output directory {}", out_dir).to_string());
+
+    let scans: Vec<FileScan> = std::thread::scope(|scope| {
+        csv_paths
+            .iter()
+            .map(|path| scope.spawn(|| scan_file(path, schema)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("Scanning thread panicked"))
+            .collect::<std::io::Result<Vec<FileScan>>>()
+    })?;
+
+    let mut state: ConversionWriters = ConversionWriters::new(&options.benign_label_name);
+    let mut max_duration_ms: i64 = 0;
+    for scan in &scans {
+        max_duration_ms = max(max_duration_ms, scan.max_duration_ms);
+        for label_name in &scan.labels {
+            if !state.label_map.contains_key(label_name) {
+                let index: u8 = next_label_index(&state.label_map)?;
+                state.label_map.insert(label_name.clone(), index);
+            }
+        }
+    }
+    let duration_str_width: u8 = duration_str_width_for_max_ms(max_duration_ms);
+
+    for csv_path in csv_paths {
+        stream_convert_one_file(csv_path, schema, options, duration_str_width, &mut state, out_dir)?;
+    }
+
+    state.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cic::CIC_IDS_2017;
+
+    /// Builds one CSV row matching `schema`'s column layout, with only the
+    /// columns the conversion pipeline actually reads filled in.
+    fn csv_row(schema: &DatasetSchema, minute: u32, label: &str) -> String {
+        let mut fields: Vec<String> = vec!["0".to_string(); schema.record_len];
+        fields[schema.src_ip_col] = "10.0.0.1".to_string();
+        fields[schema.src_port_col] = "1234".to_string();
+        fields[schema.dst_ip_col] = "10.0.0.2".to_string();
+        fields[schema.dst_port_col] = "80".to_string();
+        fields[schema.protocol_col] = "6".to_string();
+        fields[schema.timestamp_col] = format!("01/01/2019 12:{:02}", minute);
+        fields[schema.duration_col] = "1000".to_string();
+        fields[schema.fwd_packet_col] = "1".to_string();
+        fields[schema.fwd_packet_col + 1] = "1".to_string();
+        fields[schema.bwd_packet_col] = "1".to_string();
+        fields[schema.bwd_packet_col + 1] = "1".to_string();
+        fields[schema.fwd_bytes_col] = "100".to_string();
+        fields[schema.bwd_bytes_col] = "200".to_string();
+        fields[schema.label_col] = label.to_string();
+        fields.join(",")
+    }
+
+    fn test_options() -> ConvertOptions {
+        ConvertOptions {
+            is_am: Some(true),
+            start_time: None,
+            end_time: None,
+            benign_label_name: "Benign".to_string(),
+            overwrite: OverwritePolicy::Force,
+            archive: false,
+        }
+    }
+
+    #[test]
+    fn stream_convert_files_to_nf_files_merges_a_shared_label_into_one_output() {
+        let schema = &CIC_IDS_2017;
+        let dir = std::env::temp_dir().join(format!("cic2nf_pipeline_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file1 = dir.join("a.csv").display().to_string();
+        let file2 = dir.join("b.csv").display().to_string();
+        let out_dir = dir.join("out").display().to_string();
+
+        let header: String = csv_row(schema, 0, "Label");
+        std::fs::write(
+            &file1,
+            format!(
+                "{}\n{}\n{}\n",
+                header,
+                csv_row(schema, 1, "DDoS"),
+                csv_row(schema, 2, "Bot")
+            ),
+        )
+        .unwrap();
+        std::fs::write(&file2, format!("{}\n{}\n", header, csv_row(schema, 3, "DDoS"))).unwrap();
+
+        let label_map =
+            stream_convert_files_to_nf_files(&[file1, file2], schema, &test_options(), &out_dir)
+                .unwrap();
+
+        let ddos_line_count = std::fs::read_to_string(format!("{}/DDoS.nf", out_dir))
+            .unwrap()
+            .lines()
+            .count();
+        std::fs::remove_dir_all(&dir).ok();
+
+        // Each CICRecord yields two NetFlows (forward + backward), and DDoS's
+        // two records -- one from each file -- must land in the same output
+        // file, under the one index the shared label_map assigns it.
+        assert_eq!(ddos_line_count, 4);
+        assert!(label_map.contains_key("DDoS"));
+        assert!(label_map.contains_key("Bot"));
+    }
+}